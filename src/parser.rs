@@ -1,55 +1,299 @@
 use nom::{
     branch::alt,
-    bytes::complete::{escaped, is_not, tag},
+    bytes::complete::{escaped, escaped_transform, is_not, tag},
     character::complete::{char, multispace0, one_of},
-    combinator::{cut, eof, map, not, verify},
-    error::{context, VerboseError},
+    combinator::{cut, eof, map, not, opt, value, verify},
+    error::{context, ContextError, ParseError, VerboseError},
     multi::{many0, many_till},
     sequence::{preceded, separated_pair, terminated, tuple},
     IResult,
 };
+use nom_locate::LocatedSpan;
+use std::borrow::Cow;
+
+/// Input type threaded through every combinator so byte offsets survive parsing.
+pub type Span<'a> = LocatedSpan<&'a str>;
+
+/// A source region attached to [`Element::Text`] and [`Block`] so a renderer or
+/// diagnostic can map parsed output back onto the original input.
+///
+/// For a block, the span covers from the `[` of its head tag through the `]` of
+/// its matching `[/tag]` (not just the inner text), so nested-block highlighting
+/// nests correctly.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SourceSpan {
+    pub offset: usize,
+    pub line: u32,
+    pub column: usize,
+    pub len: usize,
+}
+
+impl SourceSpan {
+    fn enclosing(start: Span, end: Span) -> Self {
+        SourceSpan {
+            offset: start.location_offset(),
+            line: start.location_line(),
+            column: start.get_utf8_column(),
+            len: end.location_offset() - start.location_offset(),
+        }
+    }
+}
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Element<'a> {
-    Text(&'a str),
+    Text(&'a str, SourceSpan),
     Block(Block<'a>),
+    /// A self-closing tag with no matching `[/tag]`, e.g. `[br]`, `[hr=2]`, `[icon/]`.
+    Void {
+        tag: &'a str,
+        value: Option<&'a str>,
+        span: SourceSpan,
+    },
+    /// A bare `https://...` or `http://...` link found in running text by
+    /// [`parse_linkified`].
+    Url { href: &'a str, span: SourceSpan },
+    /// A bare email address found in running text by [`parse_linkified`].
+    Email { addr: &'a str, span: SourceSpan },
+    /// A bare `@user@domain` mention found in running text by [`parse_linkified`].
+    Handle {
+        user: &'a str,
+        domain: &'a str,
+        span: SourceSpan,
+    },
     EOF,
 }
 
+/// Tag names accepted by [`void_tag`] when callers don't supply their own set.
+pub const DEFAULT_VOID_TAGS: &[&str] = &["br", "hr", "icon"];
+
+impl<'a> Element<'a> {
+    /// Un-escapes this element's text, turning the escape sequences
+    /// `escaped_str` leaves verbatim (`\n`, `\[`, `\]`, `\/`, `\=`, `\"`, `\\`)
+    /// into the characters they represent. For a `Block` this walks its
+    /// subtree and concatenates every decoded `Text` leaf; `Void` and `EOF`
+    /// decode to an empty string. Borrows when there's nothing to unescape.
+    pub fn decoded_text(&self) -> Cow<'a, str> {
+        match self {
+            Element::Text(s, _) => decode_escapes(s),
+            Element::Block(b) => b.decoded_text(),
+            Element::Void { .. }
+            | Element::Url { .. }
+            | Element::Email { .. }
+            | Element::Handle { .. }
+            | Element::EOF => Cow::Borrowed(""),
+        }
+    }
+}
+
+/// Turns the raw escape sequences left by `escaped_str` into real characters:
+/// `\n` becomes a newline, `\[`/`\]`/`\/`/`\=`/`\"` become the literal
+/// character, and `\\` becomes a single backslash. Text with no backslash is
+/// returned borrowed; only text that actually needs unescaping allocates.
+fn decode_escapes(raw: &'_ str) -> Cow<'_, str> {
+    if !raw.contains('\\') {
+        return Cow::Borrowed(raw);
+    }
+
+    fn escape_char(input: &str) -> IResult<&str, &str, ()> {
+        alt((
+            value("\n", char('n')),
+            value("[", char('[')),
+            value("]", char(']')),
+            value("/", char('/')),
+            value("=", char('=')),
+            value("\"", char('\"')),
+            value("\\", char('\\')),
+        ))(input)
+    }
+
+    match escaped_transform(is_not("\\"), '\\', escape_char)(raw) {
+        Ok((_, decoded)) => Cow::Owned(decoded),
+        Err(_) => Cow::Borrowed(raw),
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Block<'a> {
     inner: Vec<Element<'a>>,
     tag: &'a str,
-    value: Option<&'a str>,
+    attrs: Vec<(&'a str, Option<&'a str>)>,
+    span: SourceSpan,
+}
+
+impl<'a> Block<'a> {
+    /// The first positional (valueless) attribute, e.g. `bar` in `[foo=bar]` or
+    /// `bold` in `[style color=red bold]`. Kept around so call sites written
+    /// against the old single-value `Block.value` field migrate cleanly.
+    pub fn value(&self) -> Option<&'a str> {
+        self.attrs.iter().find(|(_, v)| v.is_none()).map(|(k, _)| *k)
+    }
+
+    /// Depth-first walk decoding every `Text` leaf in this block's subtree
+    /// and concatenating them, so escape sequences never leak into rendered
+    /// output even across nested blocks.
+    pub fn decoded_text(&self) -> Cow<'a, str> {
+        match self.inner.as_slice() {
+            [] => Cow::Borrowed(""),
+            [one] => one.decoded_text(),
+            many => {
+                let mut out = String::new();
+                for element in many {
+                    out.push_str(&element.decoded_text());
+                }
+                Cow::Owned(out)
+            }
+        }
+    }
 }
 
-type ParseResult<'a, T, E = VerboseError<&'a str>> = IResult<&'a str, T, E>;
+type ParseResult<'a, T, E> = IResult<Span<'a>, T, E>;
+
+/// Shorthand for the bounds every combinator needs: enough to report a parse
+/// error and, when the caller opted into diagnostics, to accumulate the
+/// `context(...)` stack. Under the zero-overhead `()` error type the
+/// `context(...)` wrappers below compile away to no-ops.
+trait CombinatorError<'a>: ParseError<Span<'a>> + ContextError<Span<'a>> {}
+impl<'a, T> CombinatorError<'a> for T where T: ParseError<Span<'a>> + ContextError<Span<'a>> {}
+
+/// Runs `parser`, pairing its output with the [`SourceSpan`] it consumed.
+fn spanned<'a, O, E, F>(mut parser: F) -> impl FnMut(Span<'a>) -> ParseResult<'a, (O, SourceSpan), E>
+where
+    F: FnMut(Span<'a>) -> ParseResult<'a, O, E>,
+{
+    move |input: Span<'a>| {
+        let start = input;
+        let (rest, value) = parser(input)?;
+        Ok((rest, (value, SourceSpan::enclosing(start, rest))))
+    }
+}
 
-fn escaped_str(input: &str) -> ParseResult<&str> {
+fn escaped_str<'a, E: CombinatorError<'a>>(input: Span<'a>) -> ParseResult<'a, Span<'a>, E> {
     let chars = r#""\[]/="#;
 
     escaped(is_not(chars), '\\', one_of(r#""\n[]/="#))(input)
 }
 
-fn string_quoted(input: &str) -> ParseResult<&str> {
+fn string_quoted<'a, E: CombinatorError<'a>>(input: Span<'a>) -> ParseResult<'a, Span<'a>, E> {
     context(
         "string_quoted",
         preceded(char('\"'), cut(terminated(escaped_str, char('\"')))),
     )(input)
 }
 
-fn string_without_space(input: &str) -> ParseResult<&str> {
+fn string_without_space<'a, E: CombinatorError<'a>>(input: Span<'a>) -> ParseResult<'a, Span<'a>, E> {
     let chars = "\"\\[]/= \t\n\r";
     context("string_without_space", preceded(multispace0, is_not(chars)))(input)
 }
 
-fn plain_text(input: &str) -> ParseResult<Element> {
-    context("plain_text", map(escaped_str, |s: &str| Element::Text(s)))(input)
+fn plain_text<'a, E: CombinatorError<'a>>(input: Span<'a>) -> ParseResult<'a, Element<'a>, E> {
+    context(
+        "plain_text",
+        map(spanned(escaped_str), |(s, span)| {
+            Element::Text(s.fragment(), span)
+        }),
+    )(input)
+}
+
+/// Like [`escaped_str`], but doesn't stop at a bare `/`, so a running-text
+/// scheme separator (`https://...`) stays in one slice instead of being cut
+/// right after the scheme. Used only by [`parse_linkified`]'s own structural
+/// pass, which needs whole URLs intact before it can detect them.
+fn linkify_escaped_str<'a, E: CombinatorError<'a>>(input: Span<'a>) -> ParseResult<'a, Span<'a>, E> {
+    let chars = r#""\[]="#;
+
+    escaped(is_not(chars), '\\', one_of(r#""\n[]/="#))(input)
+}
+
+fn linkify_text<'a, E: CombinatorError<'a>>(input: Span<'a>) -> ParseResult<'a, Element<'a>, E> {
+    context(
+        "linkify_text",
+        map(spanned(linkify_escaped_str), |(s, span)| {
+            Element::Text(s.fragment(), span)
+        }),
+    )(input)
+}
+
+/// Like [`string_without_space`], but doesn't stop at a bare `/`, so an
+/// unquoted attribute value can hold a whole URL (`[url=https://example.com]`)
+/// instead of being cut short at the scheme separator. Used only by
+/// [`linkify_tag_head`].
+fn linkify_string_without_space<'a, E: CombinatorError<'a>>(input: Span<'a>) -> ParseResult<'a, Span<'a>, E> {
+    let chars = "\"\\[]= \t\n\r";
+    context("linkify_string_without_space", preceded(multispace0, is_not(chars)))(input)
+}
+
+fn linkify_tag_key<'a, E: CombinatorError<'a>>(input: Span<'a>) -> ParseResult<'a, Span<'a>, E> {
+    context("linkify_tag_key", linkify_string_without_space)(input)
+}
+
+fn linkify_tag_value<'a, E: CombinatorError<'a>>(input: Span<'a>) -> ParseResult<'a, Span<'a>, E> {
+    context(
+        "linkify_tag_value",
+        alt((linkify_string_without_space, string_quoted)),
+    )(input)
+}
+
+fn linkify_tag_attr<'a, E: CombinatorError<'a>>(
+    input: Span<'a>,
+) -> ParseResult<'a, (&'a str, Option<&'a str>), E> {
+    context(
+        "linkify_tag_attr",
+        alt((
+            map(
+                separated_pair(
+                    preceded(multispace0, linkify_tag_key),
+                    preceded(multispace0, char('=')),
+                    preceded(multispace0, linkify_tag_value),
+                ),
+                |(k, v): (Span, Span)| (*k.fragment(), Some(*v.fragment())),
+            ),
+            map(preceded(multispace0, linkify_tag_key), |s: Span| {
+                (*s.fragment(), None)
+            }),
+        )),
+    )(input)
+}
+
+/// Like [`tag_head`], but its attribute values are parsed with
+/// [`linkify_tag_value`] so a bare URL survives intact. Used only by
+/// [`parse_linkified`]'s own structural pass.
+fn linkify_tag_head<'a, E: CombinatorError<'a>>(
+    input: Span<'a>,
+) -> ParseResult<'a, (&'a str, Vec<(&'a str, Option<&'a str>)>), E> {
+    context(
+        "linkify_tag_head",
+        preceded(
+            tuple((char('['), not(char('/')))),
+            cut(terminated(
+                map(
+                    tuple((
+                        preceded(multispace0, linkify_tag_key),
+                        opt(preceded(
+                            preceded(multispace0, char('=')),
+                            preceded(multispace0, linkify_tag_value),
+                        )),
+                        many0(preceded(multispace0, linkify_tag_attr)),
+                    )),
+                    |(name, direct_value, rest): (Span, Option<Span>, Vec<(&str, Option<&str>)>)| {
+                        let mut attrs = Vec::with_capacity(rest.len() + 1);
+                        attrs.extend(direct_value.map(|v| (*v.fragment(), None)));
+                        attrs.extend(rest);
+                        (*name.fragment(), attrs)
+                    },
+                ),
+                preceded(tuple((multispace0, opt(char('/')))), preceded(multispace0, char(']'))),
+            )),
+        ),
+    )(input)
 }
 
-fn tag_head_keypair(input: &str) -> ParseResult<(&str, Option<&str>)> {
+/// A single `key` or `key=value` attribute, as found after a tag's name in its
+/// head, e.g. `color=red` or `bold` in `[style color=red bold]`.
+fn tag_attr<'a, E: CombinatorError<'a>>(
+    input: Span<'a>,
+) -> ParseResult<'a, (&'a str, Option<&'a str>), E> {
     context(
-        "tag_head_keypair",
+        "tag_attr",
         alt((
             map(
                 separated_pair(
@@ -57,97 +301,480 @@ fn tag_head_keypair(input: &str) -> ParseResult<(&str, Option<&str>)> {
                     preceded(multispace0, char('=')),
                     preceded(multispace0, tag_value),
                 ),
-                |(k, v)| (k, Some(v)),
+                |(k, v): (Span, Span)| (*k.fragment(), Some(*v.fragment())),
             ),
-            map(preceded(multispace0, tag_key), |s| (s, None)),
+            map(preceded(multispace0, tag_key), |s: Span| (*s.fragment(), None)),
         )),
     )(input)
 }
 
-fn tag_key(input: &str) -> ParseResult<&str> {
+fn tag_key<'a, E: CombinatorError<'a>>(input: Span<'a>) -> ParseResult<'a, Span<'a>, E> {
     context("tag_key", string_without_space)(input)
 }
 
-fn tag_value(input: &str) -> ParseResult<&str> {
+fn tag_value<'a, E: CombinatorError<'a>>(input: Span<'a>) -> ParseResult<'a, Span<'a>, E> {
     context("tag_value", alt((string_without_space, string_quoted)))(input)
 }
 
-fn tag_head(input: &str) -> ParseResult<(&str, Option<&str>)> {
+/// Parses `name`, then an optional value glued directly onto it (the legacy
+/// `[foo=bar]` single-value form, kept as the first attribute so it still
+/// round-trips through [`Block::value`]), then any number of further
+/// whitespace-separated `key`/`key=value` attributes: `[style color=red size=64 bold]`.
+fn tag_head<'a, E: CombinatorError<'a>>(
+    input: Span<'a>,
+) -> ParseResult<'a, (&'a str, Vec<(&'a str, Option<&'a str>)>), E> {
     context(
         "tag_head",
         preceded(
             tuple((char('['), not(char('/')))),
             cut(terminated(
-                tag_head_keypair,
-                preceded(multispace0, char(']')),
+                map(
+                    tuple((
+                        preceded(multispace0, tag_key),
+                        opt(preceded(
+                            preceded(multispace0, char('=')),
+                            preceded(multispace0, tag_value),
+                        )),
+                        many0(preceded(multispace0, tag_attr)),
+                    )),
+                    |(name, direct_value, rest): (Span, Option<Span>, Vec<(&str, Option<&str>)>)| {
+                        let mut attrs = Vec::with_capacity(rest.len() + 1);
+                        attrs.extend(direct_value.map(|v| (*v.fragment(), None)));
+                        attrs.extend(rest);
+                        (*name.fragment(), attrs)
+                    },
+                ),
+                // Tolerate the explicit `[name/]` void-tag slash here too, so a
+                // head that turns out to be a void tag doesn't commit to
+                // `Err::Failure` via `cut` before `element` gets a chance to
+                // retry it as `void_tag`.
+                preceded(tuple((multispace0, opt(char('/')))), preceded(multispace0, char(']'))),
             )),
         ),
     )(input)
 }
 
-fn tag_end(input: &str) -> ParseResult<&str> {
+fn tag_end<'a, E: CombinatorError<'a>>(input: Span<'a>) -> ParseResult<'a, &'a str, E> {
     context(
         "tag_end",
-        preceded(
-            tag("[/"),
-            cut(terminated(tag_value, preceded(multispace0, char(']')))),
+        map(
+            preceded(
+                tag("[/"),
+                cut(terminated(tag_value, preceded(multispace0, char(']')))),
+            ),
+            |s: Span| *s.fragment(),
         ),
     )(input)
 }
 
-fn closed_tag(input: &str) -> ParseResult<(&str, Option<&str>, Vec<Element>)> {
-    context(
-        "closed_tag",
-        map(
+fn closed_tag<'a, E: CombinatorError<'a>, HF, TF>(
+    void_tags: &'a [&'a str],
+    head: HF,
+    text: TF,
+) -> impl FnMut(
+    Span<'a>,
+) -> ParseResult<
+    'a,
+    (
+        &'a str,
+        Vec<(&'a str, Option<&'a str>)>,
+        Vec<Element<'a>>,
+        SourceSpan,
+    ),
+    E,
+>
+where
+    HF: Fn(Span<'a>) -> ParseResult<'a, (&'a str, Vec<(&'a str, Option<&'a str>)>), E> + Copy + 'a,
+    TF: Fn(Span<'a>) -> ParseResult<'a, Element<'a>, E> + Copy + 'a,
+{
+    move |input: Span<'a>| {
+        context(
+            "closed_tag",
+            map(
+                verify(
+                    spanned(tuple((head, elements(void_tags, head, text), tag_end))),
+                    |((head, _, end_key), _span)| head.0 == *end_key,
+                ),
+                |((head, inner, _end), span)| (head.0, head.1, inner, span),
+            ),
+        )(input)
+    }
+}
+
+/// A self-closing tag, e.g. `[br]`, `[icon=star]`, or the explicit `[hr/]` form.
+/// Unlike [`closed_tag`] it never looks for a matching `[/tag]`; `void_tags`
+/// restricts which bare tag names are accepted this way so an unknown bare
+/// `[foo]` still fails to parse instead of silently swallowing what follows.
+fn void_tag<'a, E: CombinatorError<'a>>(
+    void_tags: &'a [&'a str],
+) -> impl FnMut(Span<'a>) -> ParseResult<'a, ((&'a str, Option<&'a str>), SourceSpan), E> {
+    move |input: Span<'a>| {
+        context(
+            "void_tag",
             verify(
-                tuple((tag_head, elements, tag_end)),
-                |&((head_key, _), _, end_key)| head_key == end_key,
+                spanned(preceded(
+                    tuple((char('['), not(char('/')))),
+                    cut(terminated(
+                        tag_attr,
+                        tuple((multispace0, opt(char('/')), multispace0, char(']'))),
+                    )),
+                )),
+                |((key, _), _span): &((&str, Option<&str>), SourceSpan)| void_tags.contains(key),
             ),
-            |((key, value), inner, _)| (key, value, inner),
-        ),
-    )(input)
+        )(input)
+    }
 }
 
-fn block(input: &str) -> ParseResult<Element> {
-    context(
-        "block",
-        map(closed_tag, |(key, value, inner)| {
-            Element::Block(Block {
-                inner,
-                tag: key,
-                value: value.and_then(|s| Some(s)),
-            })
-        }),
-    )(input)
+fn block<'a, E: CombinatorError<'a>, HF, TF>(
+    void_tags: &'a [&'a str],
+    head: HF,
+    text: TF,
+) -> impl FnMut(Span<'a>) -> ParseResult<'a, Element<'a>, E>
+where
+    HF: Fn(Span<'a>) -> ParseResult<'a, (&'a str, Vec<(&'a str, Option<&'a str>)>), E> + Copy + 'a,
+    TF: Fn(Span<'a>) -> ParseResult<'a, Element<'a>, E> + Copy + 'a,
+{
+    move |input: Span<'a>| {
+        context(
+            "block",
+            map(
+                closed_tag(void_tags, head, text),
+                |(key, attrs, inner, span)| {
+                    Element::Block(Block {
+                        inner,
+                        tag: key,
+                        attrs,
+                        span,
+                    })
+                },
+            ),
+        )(input)
+    }
 }
 
-fn element(input: &str) -> ParseResult<Element> {
-    context(
-        "element",
-        alt((map(eof, |_| Element::EOF), plain_text, block)),
-    )(input)
+/// The atom `elements`/`block`/`closed_tag` recurse over. `head` and `text`
+/// are threaded through (rather than hard-coded to [`tag_head`]/[`plain_text`])
+/// so [`parse_linkified`] can swap in [`linkify_tag_head`]/[`linkify_text`],
+/// which keep bare URLs intact, without duplicating the rest of the grammar.
+fn element<'a, E: CombinatorError<'a>, HF, TF>(
+    void_tags: &'a [&'a str],
+    head: HF,
+    text: TF,
+) -> impl FnMut(Span<'a>) -> ParseResult<'a, Element<'a>, E>
+where
+    HF: Fn(Span<'a>) -> ParseResult<'a, (&'a str, Vec<(&'a str, Option<&'a str>)>), E> + Copy + 'a,
+    TF: Fn(Span<'a>) -> ParseResult<'a, Element<'a>, E> + Copy + 'a,
+{
+    move |input: Span<'a>| {
+        context(
+            "element",
+            alt((
+                map(eof, |_| Element::EOF),
+                text,
+                block(void_tags, head, text),
+                map(void_tag(void_tags), |((tag, value), span)| Element::Void {
+                    tag,
+                    value,
+                    span,
+                }),
+            )),
+        )(input)
+    }
+}
+
+fn elements<'a, E: CombinatorError<'a>, HF, TF>(
+    void_tags: &'a [&'a str],
+    head: HF,
+    text: TF,
+) -> impl FnMut(Span<'a>) -> ParseResult<'a, Vec<Element<'a>>, E>
+where
+    HF: Fn(Span<'a>) -> ParseResult<'a, (&'a str, Vec<(&'a str, Option<&'a str>)>), E> + Copy + 'a,
+    TF: Fn(Span<'a>) -> ParseResult<'a, Element<'a>, E> + Copy + 'a,
+{
+    move |input: Span<'a>| context("elements", many0(element(void_tags, head, text)))(input)
 }
 
-fn elements(input: &str) -> ParseResult<Vec<Element>> {
-    context("elements", many0(element))(input)
+fn parse_with<'a, E: CombinatorError<'a> + 'a>(
+    input: Span<'a>,
+    void_tags: &'a [&'a str],
+) -> ParseResult<'a, Vec<Element<'a>>, E> {
+    let (input, (r, _)) =
+        context("elements", many_till(element(void_tags, tag_head, plain_text), eof))(input)?;
+    Ok((input, r))
 }
 
-pub fn parse(input: &str) -> ParseResult<Vec<Element>> {
-    let (input, (r, _)) = context("elements", many_till(element, eof))(input)?;
+fn parse_linkify_structural<'a, E: CombinatorError<'a> + 'a>(
+    input: Span<'a>,
+    void_tags: &'a [&'a str],
+) -> ParseResult<'a, Vec<Element<'a>>, E> {
+    let (input, (r, _)) = context(
+        "elements",
+        many_till(element(void_tags, linkify_tag_head, linkify_text), eof),
+    )(input)?;
     Ok((input, r))
 }
 
+/// Hot path: parses without accumulating diagnostic context, for callers (e.g.
+/// re-rendering a chat message every frame) that don't need error detail.
+/// Recognizes [`DEFAULT_VOID_TAGS`]; use [`parse_with_void_tags`] to customize.
+pub fn parse(input: &'_ str) -> ParseResult<'_, Vec<Element<'_>>, ()> {
+    parse_with(Span::new(input), DEFAULT_VOID_TAGS)
+}
+
+/// Diagnostic path: same grammar, but accumulates a `context(...)` stack. The
+/// error's entries hold [`Span`]s; `nom::error::convert_error` takes a
+/// `VerboseError<&str>`, so map each entry to its `fragment()` before rendering.
+pub fn parse_verbose(input: &'_ str) -> ParseResult<'_, Vec<Element<'_>>, VerboseError<Span<'_>>> {
+    parse_with(Span::new(input), DEFAULT_VOID_TAGS)
+}
+
+/// Like [`parse`], but accepts a caller-supplied set of void (self-closing)
+/// tag names instead of [`DEFAULT_VOID_TAGS`].
+pub fn parse_with_void_tags<'a>(
+    input: &'a str,
+    void_tags: &'a [&'a str],
+) -> ParseResult<'a, Vec<Element<'a>>, ()> {
+    parse_with(Span::new(input), void_tags)
+}
+
+/// Toggles for the detectors [`parse_linkified`] runs over plain text. All on
+/// by default; set a field to `false` to skip that detector entirely.
+#[derive(Debug, Clone, Copy)]
+pub struct LinkifyOptions {
+    pub urls: bool,
+    pub emails: bool,
+    pub handles: bool,
+}
+
+impl Default for LinkifyOptions {
+    fn default() -> Self {
+        LinkifyOptions {
+            urls: true,
+            emails: true,
+            handles: true,
+        }
+    }
+}
+
+/// Parses `input` structurally like [`parse`] — same tags, same void-tag and
+/// attribute handling — then scans every `Text` element for bare URLs, email
+/// addresses, and `@handle@domain` mentions, splitting matches out into
+/// [`Element::Url`], [`Element::Email`], and [`Element::Handle`]. Detection
+/// never fires inside a `[url]` block's own value, and the surrounding
+/// whitespace of a split text run is preserved exactly.
+///
+/// Unlike `parse`, the structural pass here doesn't stop at a bare `/` in
+/// running text or in an unquoted attribute value (see [`linkify_text`] and
+/// [`linkify_tag_head`]) — otherwise a scheme separator like `https://` would
+/// end the text or value before the rest of the URL, leaving nothing for the
+/// linkify pass to detect.
+pub fn parse_linkified<'a>(
+    input: &'a str,
+    opts: LinkifyOptions,
+) -> ParseResult<'a, Vec<Element<'a>>, ()> {
+    let (rest, elements) = parse_linkify_structural(Span::new(input), DEFAULT_VOID_TAGS)?;
+    Ok((rest, linkify_elements(elements, opts)))
+}
+
+fn linkify_elements<'a>(elements: Vec<Element<'a>>, opts: LinkifyOptions) -> Vec<Element<'a>> {
+    elements
+        .into_iter()
+        .flat_map(|element| linkify_element(element, opts))
+        .collect()
+}
+
+fn linkify_element<'a>(element: Element<'a>, opts: LinkifyOptions) -> Vec<Element<'a>> {
+    match element {
+        Element::Text(s, span) => split_linkified_text(s, span, opts),
+        Element::Block(mut b) => {
+            if b.tag != "url" {
+                b.inner = linkify_elements(b.inner, opts);
+            }
+            vec![Element::Block(b)]
+        }
+        other => vec![other],
+    }
+}
+
+/// Splits `s` into alternating plain-text and linkified pieces, preserving
+/// every byte of whitespace between matches exactly (each piece is a
+/// contiguous slice of `s`, never reformatted).
+fn split_linkified_text<'a>(
+    s: &'a str,
+    span: SourceSpan,
+    opts: LinkifyOptions,
+) -> Vec<Element<'a>> {
+    let bytes = s.as_bytes();
+    let len = s.len();
+    let mut out = Vec::new();
+    let mut plain_start = 0usize;
+    let mut idx = 0usize;
+
+    while idx < len {
+        if bytes[idx].is_ascii_whitespace() {
+            idx += 1;
+            continue;
+        }
+        let word_start = idx;
+        while idx < len && !bytes[idx].is_ascii_whitespace() {
+            idx += 1;
+        }
+        let word = &s[word_start..idx];
+
+        let word_span = derive_span(span, &s[..word_start], word);
+        if let Some(element) = classify_word(word, word_span, opts) {
+            if word_start > plain_start {
+                let plain = &s[plain_start..word_start];
+                out.push(Element::Text(plain, derive_span(span, &s[..plain_start], plain)));
+            }
+            out.push(element);
+            plain_start = idx;
+        }
+    }
+
+    if plain_start < len || out.is_empty() {
+        let plain = &s[plain_start..];
+        out.push(Element::Text(plain, derive_span(span, &s[..plain_start], plain)));
+    }
+    out
+}
+
+fn classify_word(word: &'_ str, word_span: SourceSpan, opts: LinkifyOptions) -> Option<Element<'_>> {
+    if opts.urls && (word.starts_with("http://") || word.starts_with("https://")) {
+        return Some(Element::Url {
+            href: word,
+            span: word_span,
+        });
+    }
+    if opts.handles {
+        if let Some(rest) = word.strip_prefix('@') {
+            if let Some((user, domain)) = rest.split_once('@') {
+                if !user.is_empty() && !domain.is_empty() && domain.contains('.') {
+                    return Some(Element::Handle {
+                        user,
+                        domain,
+                        span: word_span,
+                    });
+                }
+            }
+        }
+    }
+    if opts.emails {
+        if let Some((local, domain)) = word.split_once('@') {
+            if !local.is_empty()
+                && !domain.is_empty()
+                && !local.contains('@')
+                && domain.contains('.')
+                && !domain.starts_with('.')
+                && !domain.ends_with('.')
+            {
+                return Some(Element::Email {
+                    addr: word,
+                    span: word_span,
+                });
+            }
+        }
+    }
+    None
+}
+
+/// Computes the [`SourceSpan`] of `matched`, a substring of some text whose
+/// own span was `parent` and which is preceded within that text by `prefix`.
+fn derive_span(parent: SourceSpan, prefix: &str, matched: &str) -> SourceSpan {
+    let newlines = prefix.matches('\n').count() as u32;
+    let column = if newlines == 0 {
+        parent.column + prefix.chars().count()
+    } else {
+        prefix.rsplit('\n').next().unwrap_or("").chars().count() + 1
+    };
+    SourceSpan {
+        offset: parent.offset + prefix.len(),
+        line: parent.line + newlines,
+        column,
+        len: matched.len(),
+    }
+}
+
+/// A contiguous run of text plus the fully-resolved set of tags/attributes
+/// active over it, as produced by [`flatten`]. `styles` lists, for each
+/// enclosing block from outermost to innermost, `(tag, None)` followed by that
+/// block's attributes in head order (so `[foo=bar]` contributes
+/// `("foo", None), ("bar", None)`); a shaper applying them in order lets a
+/// later (more nested) entry override an earlier one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StyledRun<'a> {
+    pub text: Cow<'a, str>,
+    pub styles: Vec<(&'a str, Option<&'a str>)>,
+}
+
+/// Walks `elements` depth-first, turning the nested `Block` tree into a flat
+/// sequence of [`StyledRun`]s for a text shaper: each `Text` leaf becomes a
+/// run carrying the stack of tags/attributes active at that point, and adjacent
+/// runs with identical style stacks are coalesced into one.
+pub fn flatten<'a>(elements: &[Element<'a>]) -> Vec<StyledRun<'a>> {
+    let mut runs = Vec::new();
+    let mut stack = Vec::new();
+    flatten_into(elements, &mut stack, &mut runs);
+    runs
+}
+
+fn flatten_into<'a>(
+    elements: &[Element<'a>],
+    stack: &mut Vec<(&'a str, Option<&'a str>)>,
+    runs: &mut Vec<StyledRun<'a>>,
+) {
+    for element in elements {
+        match element {
+            Element::Text(s, _) => push_run(runs, s, stack),
+            Element::Block(b) => {
+                let depth = stack.len();
+                stack.push((b.tag, None));
+                stack.extend(b.attrs.iter().copied());
+                flatten_into(&b.inner, stack, runs);
+                stack.truncate(depth);
+            }
+            Element::Void { .. }
+            | Element::Url { .. }
+            | Element::Email { .. }
+            | Element::Handle { .. }
+            | Element::EOF => {}
+        }
+    }
+}
+
+fn push_run<'a>(runs: &mut Vec<StyledRun<'a>>, text: &'a str, stack: &[(&'a str, Option<&'a str>)]) {
+    if let Some(last) = runs.last_mut() {
+        if last.styles.as_slice() == stack {
+            let mut merged = last.text.to_string();
+            merged.push_str(text);
+            last.text = Cow::Owned(merged);
+            return;
+        }
+    }
+    runs.push(StyledRun {
+        text: Cow::Borrowed(text),
+        styles: stack.to_vec(),
+    });
+}
+
 #[test]
 fn parse_text() {
     use nom::error::convert_error;
-    // let input = "泽材[fillColor=0xff6600]灭[bold]逐[/bold][/fillColor]莫笔[strokeEnable=true]亡[/strokeEnable]鲜，[strokeEnable=true][strokeColor=black][fillColor=red][fontSize=64]如何[/fontSize][fillColor=orange][italic]气[/italic][fillColor=yellow][bold]死[/bold][fillColor=green]你的[fillColor=0xff6600]设[fillColor=blue]计师[fillColor=magenta][fontSize=28]朋[/fontSize]友[/fillColor][/fillColor][/fillColor][/fillColor][/fillColor][/fillColor][/fillColor][/strokeColor][/strokeEnable]";
-
     let input = r#"ssf[xx="123"]aaa[/xx]"#;
 
-    match parse(input) {
+    match parse_verbose(input) {
         Ok(r) => println!("{:#?}", r.1),
         Err(nom::Err::Error(e)) | Err(nom::Err::Failure(e)) => {
             println!("{}", e);
+            // convert_error wants a plain `&str`-keyed error, not the `Span`s
+            // VerboseError collected while parsing.
+            let e = VerboseError {
+                errors: e
+                    .errors
+                    .into_iter()
+                    .map(|(span, kind)| (*span.fragment(), kind))
+                    .collect(),
+            };
             println!("{}", convert_error(input, e));
         }
         Err(nom::Err::Incomplete(n)) => {
@@ -158,144 +785,439 @@ fn parse_text() {
 
 #[test]
 fn test_plain_text() {
-    assert_eq!(
-        parse(" some text ").unwrap().1,
-        vec![Element::Text(" some text ")]
-    );
+    let input = " some text ";
+    let elements = parse(input).unwrap().1;
+    match elements.as_slice() {
+        [Element::Text(s, span)] => {
+            assert_eq!(*s, input);
+            assert_eq!(span.offset, 0);
+            assert_eq!(span.len, input.len());
+        }
+        other => panic!("unexpected parse: {:?}", other),
+    }
 }
 
 #[test]
 fn test_plain_text_escaped() {
+    let input = r" some \n \[text ";
     assert_eq!(
-        parse(r" some \n \[text ").unwrap().1,
-        vec![Element::Text(r" some \n \[text ")]
+        parse(input).unwrap().1[0],
+        Element::Text(
+            input,
+            SourceSpan {
+                offset: 0,
+                line: 1,
+                column: 1,
+                len: input.len(),
+            }
+        )
     );
 }
 
 #[test]
 fn test_single_block_without_value() {
-    assert_eq!(
-        parse(r"[foo]text[/foo]").unwrap().1,
-        vec![Element::Block(Block {
-            inner: vec![Element::Text("text")],
-            tag: "foo",
-            value: None
-        })]
-    );
+    let input = r"[foo]text[/foo]";
+    let elements = parse(input).unwrap().1;
+    match elements.as_slice() {
+        [Element::Block(b)] => {
+            assert_eq!(b.tag, "foo");
+            assert_eq!(b.value(), None);
+            match b.inner.as_slice() {
+                [Element::Text(s, _)] => assert_eq!(*s, "text"),
+                other => panic!("unexpected inner: {:?}", other),
+            }
+            assert_eq!(b.span.offset, 0);
+            assert_eq!(b.span.len, input.len());
+        }
+        other => panic!("unexpected parse: {:?}", other),
+    }
 }
 
 #[test]
 fn test_single_block_with_value() {
-    assert_eq!(
-        parse(r"[foo=bar]text[/foo]").unwrap().1,
-        vec![Element::Block(Block {
-            inner: vec![Element::Text("text")],
-            tag: "foo",
-            value: Some("bar")
-        })]
-    );
+    let input = r"[foo=bar]text[/foo]";
+    let elements = parse(input).unwrap().1;
+    match elements.as_slice() {
+        [Element::Block(b)] => {
+            assert_eq!(b.tag, "foo");
+            assert_eq!(b.value(), Some("bar"));
+            assert_eq!(b.span.offset, 0);
+            assert_eq!(b.span.len, input.len());
+        }
+        other => panic!("unexpected parse: {:?}", other),
+    }
 }
 
 #[test]
 fn test_single_block_with_value_quoted() {
+    let input = r#"[foo="bar "]text[/foo]"#;
+    let elements = parse(input).unwrap().1;
+    match elements.as_slice() {
+        [Element::Block(b)] => {
+            assert_eq!(b.tag, "foo");
+            assert_eq!(b.value(), Some("bar "));
+        }
+        other => panic!("unexpected parse: {:?}", other),
+    }
+}
+
+#[test]
+fn test_single_block_multiline() {
+    let input = "[foo=bar]\ntext\n  \n[/foo]";
+    let elements = parse(input).unwrap().1;
+    match elements.as_slice() {
+        [Element::Block(b)] => {
+            assert_eq!(b.tag, "foo");
+            assert_eq!(b.value(), Some("bar"));
+            match b.inner.as_slice() {
+                [Element::Text(s, _)] => assert_eq!(*s, "\ntext\n  \n"),
+                other => panic!("unexpected inner: {:?}", other),
+            }
+        }
+        other => panic!("unexpected parse: {:?}", other),
+    }
+}
+
+#[test]
+fn test_mixed_text_and_block() {
+    let input = r" some text [foo=bar]text[/foo]";
+    let elements = parse(input).unwrap().1;
+    match elements.as_slice() {
+        [Element::Text(s, text_span), Element::Block(b)] => {
+            assert_eq!(*s, " some text ");
+            assert_eq!(text_span.offset, 0);
+            assert_eq!(b.tag, "foo");
+            assert_eq!(b.value(), Some("bar"));
+            assert_eq!(b.span.offset, " some text ".len());
+        }
+        other => panic!("unexpected parse: {:?}", other),
+    }
+}
+
+#[test]
+fn test_nested_blocks() {
+    let input = r"[foo=bar][xx=123][/xx][/foo]";
+    let elements = parse(input).unwrap().1;
+    match elements.as_slice() {
+        [Element::Block(outer)] => {
+            assert_eq!(outer.tag, "foo");
+            assert_eq!(outer.span.offset, 0);
+            assert_eq!(outer.span.len, input.len());
+            match outer.inner.as_slice() {
+                [Element::Block(inner)] => {
+                    assert_eq!(inner.tag, "xx");
+                    assert_eq!(inner.value(), Some("123"));
+                    // The nested block's span must be fully contained within its
+                    // parent's span, covering through its own `[/xx]`.
+                    assert!(inner.span.offset >= outer.span.offset);
+                    assert_eq!(inner.span.offset, "[foo=bar]".len());
+                    assert_eq!(inner.span.len, "[xx=123][/xx]".len());
+                    assert!(
+                        inner.span.offset + inner.span.len
+                            <= outer.span.offset + outer.span.len
+                    );
+                }
+                other => panic!("unexpected inner: {:?}", other),
+            }
+        }
+        other => panic!("unexpected parse: {:?}", other),
+    }
+}
+
+#[test]
+fn test_complex_elements() {
+    let input = r"a\n[foo=bar]q[xx=123][/xx]x[/foo][yy][/yy]";
+    let elements = parse(input).unwrap().1;
+    match elements.as_slice() {
+        [Element::Text(s, _), Element::Block(foo), Element::Block(yy)] => {
+            assert_eq!(*s, r"a\n");
+            assert_eq!(foo.tag, "foo");
+            assert_eq!(foo.value(), Some("bar"));
+            assert_eq!(yy.tag, "yy");
+            assert_eq!(yy.value(), None);
+            match foo.inner.as_slice() {
+                [Element::Text(q, _), Element::Block(xx), Element::Text(x, _)] => {
+                    assert_eq!(*q, "q");
+                    assert_eq!(xx.tag, "xx");
+                    assert_eq!(xx.value(), Some("123"));
+                    assert_eq!(*x, "x");
+                }
+                other => panic!("unexpected foo.inner: {:?}", other),
+            }
+        }
+        other => panic!("unexpected parse: {:?}", other),
+    }
+}
+
+#[test]
+fn test_tagpair_with_spaces() {
+    let input = r#"[ foo = "bar " ]text[/ foo  ]"#;
+    let elements = parse(input).unwrap().1;
+    match elements.as_slice() {
+        [Element::Block(b)] => {
+            assert_eq!(b.tag, "foo");
+            assert_eq!(b.value(), Some("bar "));
+            assert_eq!(b.span.offset, 0);
+            assert_eq!(b.span.len, input.len());
+        }
+        other => panic!("unexpected parse: {:?}", other),
+    }
+}
+
+#[test]
+fn test_void_tag_between_text() {
     assert_eq!(
-        parse(r#"[foo="bar "]text[/foo]"#).unwrap().1,
-        vec![Element::Block(Block {
-            inner: vec![Element::Text("text")],
-            tag: "foo",
-            value: Some("bar ")
-        })]
+        parse("a[br]b").unwrap().1,
+        vec![
+            Element::Text("a", SourceSpan { offset: 0, line: 1, column: 1, len: 1 }),
+            Element::Void {
+                tag: "br",
+                value: None,
+                span: SourceSpan { offset: 1, line: 1, column: 2, len: 4 },
+            },
+            Element::Text("b", SourceSpan { offset: 5, line: 1, column: 6, len: 1 }),
+        ]
     );
 }
 
 #[test]
-fn test_single_block_multiline() {
+fn test_void_tag_with_value_and_explicit_slash() {
     assert_eq!(
-        parse(
-            r"[foo=bar]
-text
-  
-[/foo]"
-        )
+        parse("[icon=star/]").unwrap().1,
+        vec![Element::Void {
+            tag: "icon",
+            value: Some("star"),
+            span: SourceSpan { offset: 0, line: 1, column: 1, len: 12 },
+        }]
+    );
+}
+
+#[test]
+fn test_unknown_bare_tag_is_not_treated_as_void() {
+    // `foo` isn't in the void set and is never closed, so this must fail
+    // rather than being silently swallowed as a void tag.
+    assert!(parse("[foo]text").is_err());
+}
+
+#[test]
+fn test_multiple_attributes() {
+    let input = r"[style color=red size=64 bold]text[/style]";
+    let elements = parse(input).unwrap().1;
+    match elements.as_slice() {
+        [Element::Block(b)] => {
+            assert_eq!(b.tag, "style");
+            assert_eq!(
+                b.attrs,
+                vec![("color", Some("red")), ("size", Some("64")), ("bold", None)]
+            );
+            assert_eq!(b.value(), Some("bold"));
+        }
+        other => panic!("unexpected parse: {:?}", other),
+    }
+}
+
+#[test]
+fn test_decoded_text_unescapes() {
+    let input = r" some \n \[text ";
+    let elements = parse(input).unwrap().1;
+    assert_eq!(elements[0].decoded_text(), " some \n [text ");
+}
+
+#[test]
+fn test_decoded_text_borrows_when_no_escapes() {
+    let input = "plain text";
+    let elements = parse(input).unwrap().1;
+    match elements[0].decoded_text() {
+        Cow::Borrowed(s) => assert_eq!(s, input),
+        Cow::Owned(s) => panic!("expected a borrow, got an owned string: {:?}", s),
+    }
+}
+
+#[test]
+fn test_decoded_text_walks_nested_blocks() {
+    let input = r"[foo]a\nb[bar]c\[d[/bar]e[/foo]";
+    let elements = parse(input).unwrap().1;
+    match elements.as_slice() {
+        [Element::Block(b)] => assert_eq!(b.decoded_text(), "a\nbc[de"),
+        other => panic!("unexpected parse: {:?}", other),
+    }
+}
+
+#[test]
+fn test_linkify_url_in_plain_text() {
+    let opts = LinkifyOptions::default();
+    let elements = parse_linkified("see https://example.com/path now", opts)
         .unwrap()
-        .1,
-        vec![Element::Block(Block {
-            inner: vec![Element::Text("\ntext\n  \n")],
-            tag: "foo",
-            value: Some("bar")
-        })]
+        .1;
+    assert_eq!(
+        elements,
+        vec![
+            Element::Text(
+                "see ",
+                SourceSpan {
+                    offset: 0,
+                    line: 1,
+                    column: 1,
+                    len: 4
+                }
+            ),
+            Element::Url {
+                href: "https://example.com/path",
+                span: SourceSpan {
+                    offset: 4,
+                    line: 1,
+                    column: 5,
+                    len: 24
+                }
+            },
+            Element::Text(
+                " now",
+                SourceSpan {
+                    offset: 28,
+                    line: 1,
+                    column: 29,
+                    len: 4
+                }
+            ),
+        ]
     );
 }
 
 #[test]
-fn test_mixed_text_and_block() {
+fn test_linkify_email_and_handle() {
+    let opts = LinkifyOptions::default();
+    let elements = parse_linkified("ping a@b.com or @alice@example.social", opts)
+        .unwrap()
+        .1;
+    match elements.as_slice() {
+        [
+            Element::Text(_, _),
+            Element::Email { addr, .. },
+            Element::Text(_, _),
+            Element::Handle { user, domain, .. },
+        ] => {
+            assert_eq!(*addr, "a@b.com");
+            assert_eq!(*user, "alice");
+            assert_eq!(*domain, "example.social");
+        }
+        other => panic!("unexpected parse: {:?}", other),
+    }
+}
+
+#[test]
+fn test_linkify_skips_opted_out_detectors() {
+    let opts = LinkifyOptions {
+        urls: false,
+        emails: true,
+        handles: true,
+    };
+    let elements = parse_linkified("https://example.com", opts).unwrap().1;
+    match elements.as_slice() {
+        [Element::Text(s, _)] => assert_eq!(*s, "https://example.com"),
+        other => panic!("unexpected parse: {:?}", other),
+    }
+}
+
+#[test]
+fn test_linkify_skips_inside_url_block() {
+    let opts = LinkifyOptions::default();
+    let elements = parse_linkified("[url=https://example.com]https://nested.example[/url]", opts)
+        .unwrap()
+        .1;
+    match elements.as_slice() {
+        [Element::Block(b)] => {
+            assert_eq!(b.tag, "url");
+            match b.inner.as_slice() {
+                [Element::Text(s, _)] => assert_eq!(*s, "https://nested.example"),
+                other => panic!("unexpected inner: {:?}", other),
+            }
+        }
+        other => panic!("unexpected parse: {:?}", other),
+    }
+}
+
+#[test]
+fn test_linkify_preserves_whitespace_exactly() {
+    let opts = LinkifyOptions::default();
+    let input = "  https://example.com   trailing  ";
+    let elements = parse_linkified(input, opts).unwrap().1;
+    let mut rebuilt = String::new();
+    for element in &elements {
+        match element {
+            Element::Text(s, _) => rebuilt.push_str(s),
+            Element::Url { href, .. } => rebuilt.push_str(href),
+            _ => unreachable!(),
+        }
+    }
+    assert_eq!(rebuilt, input);
+}
+
+#[test]
+fn test_flatten_nested_blocks_into_runs() {
+    let input = r"[a]x[b]y[/b]z[/a]";
+    let elements = parse(input).unwrap().1;
+    let runs = flatten(&elements);
     assert_eq!(
-        parse(r" some text [foo=bar]text[/foo]").unwrap().1,
+        runs,
         vec![
-            Element::Text(" some text "),
-            Element::Block(Block {
-                inner: vec![Element::Text("text")],
-                tag: "foo",
-                value: Some("bar")
-            })
+            StyledRun {
+                text: Cow::Borrowed("x"),
+                styles: vec![("a", None)],
+            },
+            StyledRun {
+                text: Cow::Borrowed("y"),
+                styles: vec![("a", None), ("b", None)],
+            },
+            StyledRun {
+                text: Cow::Borrowed("z"),
+                styles: vec![("a", None)],
+            },
         ]
     );
 }
 
 #[test]
-fn test_nested_blocks() {
+fn test_flatten_coalesces_adjacent_same_style_runs() {
+    let input = r"[a][b]x[/b][b]y[/b][/a]";
+    let elements = parse(input).unwrap().1;
+    let runs = flatten(&elements);
     assert_eq!(
-        parse(r"[foo=bar][xx=123][/xx][/foo]").unwrap().1,
-        vec![Element::Block(Block {
-            inner: vec![Element::Block(Block {
-                inner: vec![],
-                tag: "xx",
-                value: Some("123")
-            })],
-            tag: "foo",
-            value: Some("bar")
-        })]
+        runs,
+        vec![StyledRun {
+            text: Cow::Owned("xy".to_string()),
+            styles: vec![("a", None), ("b", None)],
+        }]
     );
 }
 
 #[test]
-fn test_complex_elements() {
+fn test_flatten_multi_attribute_block_keeps_every_attr() {
+    let input = "[style color=red size=64 bold]text[/style]";
+    let elements = parse(input).unwrap().1;
+    let runs = flatten(&elements);
     assert_eq!(
-        parse(r"a\n[foo=bar]q[xx=123][/xx]x[/foo][yy][/yy]")
-            .unwrap()
-            .1,
-        vec![
-            Element::Text("a\\n"),
-            Element::Block(Block {
-                inner: vec![
-                    Element::Text("q"),
-                    Element::Block(Block {
-                        inner: vec![],
-                        tag: "xx",
-                        value: Some("123")
-                    }),
-                    Element::Text("x")
-                ],
-                tag: "foo",
-                value: Some("bar")
-            }),
-            Element::Block(Block {
-                inner: vec![],
-                tag: "yy",
-                value: None
-            })
-        ]
+        runs,
+        vec![StyledRun {
+            text: Cow::Borrowed("text"),
+            styles: vec![
+                ("style", None),
+                ("color", Some("red")),
+                ("size", Some("64")),
+                ("bold", None),
+            ],
+        }]
     );
 }
 
 #[test]
-fn test_tagpair_with_spaces() {
+fn test_flatten_plain_text_has_no_styles() {
+    let elements = parse("plain").unwrap().1;
+    let runs = flatten(&elements);
     assert_eq!(
-        parse(r#"[ foo = "bar " ]text[/ foo  ]"#).unwrap().1,
-        vec![Element::Block(Block {
-            inner: vec![Element::Text("text")],
-            tag: "foo",
-            value: Some("bar ")
-        })]
+        runs,
+        vec![StyledRun {
+            text: Cow::Borrowed("plain"),
+            styles: vec![],
+        }]
     );
 }